@@ -4,6 +4,7 @@ use std::io::{Read, Result, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// The file object that supports random access. Since in D4 file,
 /// we actually use a random access file mode, which means all the read
@@ -21,6 +22,11 @@ use std::sync::{Arc, Mutex};
 /// upper layer's responsibility to determine the correct block beginning.
 pub struct RandFile<'a, Mode: AccessMode, T: 'a> {
     inner: Arc<Mutex<IoWrapper<'a, T>>>,
+    /// An independent positional-I/O handle on the same underlying file, used
+    /// to serve reads without holding `inner`'s lock during the read itself
+    /// (the lock is still taken briefly to check the token). `None` unless the backend
+    /// was constructed via a `*_positional` helper.
+    positional: Option<Arc<dyn PositionalRead>>,
     token: u32,
     _phantom: PhantomData<Mode>,
 }
@@ -60,6 +66,19 @@ impl<T> IoWrapper<'_, T> {
             ))
         }
     }
+
+    /// Like [`try_borrow_mut`](IoWrapper::try_borrow_mut), but for backends
+    /// that only need `&self` (e.g. a boxed [`RandomAccess`]).
+    fn try_borrow(&self, token: u32) -> Result<&T> {
+        if token == self.current_token {
+            Ok(&self.inner)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Rand file locked",
+            ))
+        }
+    }
 }
 
 impl<T> Deref for IoWrapper<'_, T> {
@@ -74,6 +93,7 @@ impl<M: AccessMode, T> Clone for RandFile<'_, M, T> {
         self.inner.lock().unwrap().token_stack[self.token as usize].0 += 1;
         Self {
             inner: self.inner.clone(),
+            positional: self.positional.clone(),
             token: self.token,
             _phantom: PhantomData,
         }
@@ -92,6 +112,7 @@ impl<'a, M: AccessMode, T: 'a> RandFile<'a, M, T> {
                 token_stack: vec![(1, Box::new(|| ()))],
                 inner,
             })),
+            positional: None,
             token: 0,
             _phantom: PhantomData,
         }
@@ -108,6 +129,7 @@ impl<'a, M: AccessMode, T: 'a> RandFile<'a, M, T> {
         drop(inner);
         Ok(RandFile {
             inner: self.inner.clone(),
+            positional: self.positional.clone(),
             token,
             _phantom: PhantomData,
         })
@@ -193,6 +215,87 @@ impl<Mode: CanWrite<T>, T: Write + Seek> RandFile<'_, Mode, T> {
         Ok(ret)
     }
 }
+
+impl<Mode: AccessMode> RandFile<'_, Mode, File> {
+    /// Take an OS-level advisory lock on `[offset, offset + len)` of the
+    /// underlying file, honored across processes. Like any `fcntl` record
+    /// lock it does not exclude other threads or handles of this same
+    /// process; that is what the `token`/`Mutex` machinery above covers.
+    /// `len == 0` is the POSIX idiom for "to the end of the file, including
+    /// any future growth" — use that instead of an explicit huge length,
+    /// since `fcntl` rejects a negative `l_len`.
+    ///
+    /// Blocks for up to `timeout` waiting for the range to become available;
+    /// `None` blocks indefinitely. Returns `ErrorKind::WouldBlock` if
+    /// `timeout` expires first. The lock is released when the returned guard
+    /// is dropped.
+    pub fn lock_range(
+        &self,
+        offset: u64,
+        len: u64,
+        timeout: Option<Duration>,
+    ) -> Result<file_lock::FileLockGuard> {
+        let fd = {
+            let inner = self
+                .inner
+                .lock()
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+            file_lock::raw_fd_of(&inner)
+        };
+        file_lock::lock_range(fd, offset, len, timeout)
+    }
+}
+
+impl<Mode: CanWrite<File>> RandFile<'_, Mode, File> {
+    /// The current end of the file, used as the start of the range
+    /// [`append_block_locked`](RandFile::append_block_locked)/
+    /// [`reserve_block_locked`](RandFile::reserve_block_locked) lock. A
+    /// snapshot taken before the lock is acquired is still a safe (if
+    /// slightly conservative) choice: `lock_range`'s `len == 0` locks from
+    /// this offset to the end of the file including any future growth, so
+    /// even if another process appends between this read and the lock
+    /// being granted, the locked range still covers the real end-of-file
+    /// region once it's held.
+    fn end_of_file(&self) -> Result<u64> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        inner.try_borrow_mut(self.token)?.seek(SeekFrom::End(0))
+    }
+
+    /// Like [`append_block`](RandFile::append_block), but first takes an
+    /// advisory lock on the end-of-file region so that a second process
+    /// appending to the same D4 file cannot interleave its write with this
+    /// one. Intended for the multi-process case (parallel per-chromosome
+    /// writers, indexers); same-process callers are already serialized by the
+    /// token/mutex path and don't need this.
+    pub fn append_block_locked(&mut self, buf: &[u8], timeout: Option<Duration>) -> Result<u64> {
+        let start = self.end_of_file()?;
+        let _guard = self.lock_range(start, 0, timeout)?;
+        self.append_block(buf)
+    }
+
+    /// The locked counterpart of [`reserve_block`](RandFile::reserve_block);
+    /// see [`append_block_locked`](RandFile::append_block_locked).
+    pub fn reserve_block_locked(&mut self, size: usize, timeout: Option<Duration>) -> Result<u64> {
+        let start = self.end_of_file()?;
+        let _guard = self.lock_range(start, 0, timeout)?;
+        self.reserve_block(size)
+    }
+
+    /// Truncate (or extend with zeros) the underlying file to exactly `size`
+    /// bytes. Used by [`ring_buffer::RingBufferWriter`] to trim the
+    /// over-reserved tail page back to the real amount of data written.
+    pub fn set_len(&mut self, size: u64) -> Result<()> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        inner.try_borrow_mut(self.token)?.set_len(size)
+    }
+}
+
 impl<Mode: CanRead<T>, T: Read + Seek> RandFile<'_, Mode, T> {
     pub fn size(&mut self) -> Result<u64> {
         let mut inner = self
@@ -205,7 +308,24 @@ impl<Mode: CanRead<T>, T: Read + Seek> RandFile<'_, Mode, T> {
     /// the size of the buffer slice is equal to the number of bytes that is requesting
     /// But there might not be enough bytes available for read, thus we always return
     /// the actual number of bytes is loaded
+    ///
+    /// When this `RandFile` was built with a positional backend (see
+    /// [`for_read_only_positional`](RandFile::for_read_only_positional)), the
+    /// read goes through that positional path automatically: the shared mutex
+    /// is held only for the token check, not during the read; otherwise it
+    /// falls back to the usual seek-then-read under the shared mutex.
     pub fn read_block(&mut self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        if let Some(positional) = self.positional.as_ref() {
+            // The read itself skips the mutex, but a handle predating an
+            // active `lock()` must still be shut out like every other
+            // operation in this file honors -- so check the token (just
+            // that, not the read) under the lock first.
+            self.inner
+                .lock()
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?
+                .try_borrow(self.token)?;
+            return read_block_positional(positional.as_ref(), addr, buf);
+        }
         let mut inner = self
             .inner
             .lock()
@@ -224,6 +344,248 @@ impl<Mode: CanRead<T>, T: Read + Seek> RandFile<'_, Mode, T> {
     }
 }
 
+/// A backend capable of positional (seekless) I/O, e.g. `pread(2)`/`pwrite(2)`.
+///
+/// Unlike `Read`/`Write` combined with `Seek`, a positional read or write does not
+/// disturb any shared file cursor, so it is safe to issue from multiple threads
+/// against the same underlying file without serializing through a lock.
+pub trait PositionalRead: Send + Sync {
+    /// Read bytes starting at `offset` into `buf`, returning the number of bytes
+    /// actually read (which may be less than `buf.len()` at EOF).
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+}
+
+/// The write-side counterpart of [`PositionalRead`].
+pub trait PositionalWrite: Send + Sync {
+    /// Write `buf` at `offset`, returning the number of bytes written.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize>;
+}
+
+#[cfg(unix)]
+impl PositionalRead for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+}
+
+#[cfg(unix)]
+impl PositionalWrite for File {
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize> {
+        std::os::unix::fs::FileExt::write_at(self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl PositionalRead for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl PositionalWrite for File {
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize> {
+        std::os::windows::fs::FileExt::seek_write(self, buf, offset)
+    }
+}
+
+impl RandFile<'_, ReadOnly, File> {
+    /// Create a read-only random file backed by positional reads (`pread`)
+    /// rather than seek-then-read.
+    ///
+    /// A duplicated file descriptor is kept alongside the usual locked
+    /// backend so that [`read_block`](RandFile::read_block) services reads
+    /// without holding the shared mutex while reading; the mutex is taken
+    /// only briefly to check the handle's token, so concurrent readers of the
+    /// same D4 file contend on that check but not on the reads themselves.
+    /// [`read_block_at`](RandFile::read_block_at) exposes the same path for
+    /// callers that only have `&self`.
+    pub fn for_read_only_positional(inner: File) -> Result<Self> {
+        let positional = inner.try_clone()?;
+        let mut ret = Self::new(inner);
+        ret.positional = Some(Arc::new(positional));
+        Ok(ret)
+    }
+}
+
+/// Read via the positional backend, looping until `buf` is full or EOF.
+/// Shared by [`RandFile::read_block`] (which prefers this path automatically
+/// when one is configured) and [`RandFile::read_block_at`] (for callers that
+/// only hold `&self`).
+fn read_block_positional(
+    positional: &dyn PositionalRead,
+    addr: u64,
+    buf: &mut [u8],
+) -> Result<usize> {
+    let mut ret = 0;
+    loop {
+        let bytes_read = positional.read_at(addr + ret as u64, &mut buf[ret..])?;
+        if bytes_read == 0 {
+            break Ok(ret);
+        }
+        ret += bytes_read;
+    }
+}
+
+impl<M: AccessMode, T> RandFile<'_, M, T> {
+    /// Read a block via the positional fast path, if this `RandFile` was built
+    /// with one (see [`for_read_only_positional`](RandFile::for_read_only_positional)).
+    ///
+    /// [`read_block`](RandFile::read_block) already prefers this path on its
+    /// own, so this is only useful as a convenience for callers that hold
+    /// `&self` rather than `&mut self` (a positional read never disturbs a
+    /// shared cursor, so the read itself needs no mutex; only the token check
+    /// takes it). Returns
+    /// `None` when no positional backend is configured.
+    pub fn read_block_at(&self, addr: u64, buf: &mut [u8]) -> Option<Result<usize>> {
+        let positional = self.positional.as_ref()?;
+        // Same token check `read_block` does: a positional read must still
+        // be shut out by an active `lock()` like every other operation here.
+        if let Err(e) = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))
+            .and_then(|inner| inner.try_borrow(self.token).map(|_| ()))
+        {
+            return Some(Err(e));
+        }
+        Some(read_block_positional(positional.as_ref(), addr, buf))
+    }
+}
+
+/// An object-safe, offset-addressed storage backend.
+///
+/// Where [`PositionalRead`]/[`PositionalWrite`] let a concrete backend opt into
+/// lock-free reads, `RandomAccess` lets the backend itself be chosen at
+/// runtime: `RandFile` is normally generic over a concrete `T: Read + Write +
+/// Seek`, which bakes the backend into the type. Boxing it behind
+/// `RandomAccess` (the same idea as boxing `Read`/`Write`) means an HTTP
+/// range-backed reader, an S3 object, or an in-memory page cache can all stand
+/// in for a `File` without changing the D4 reader/writer layers, which only
+/// ever call `read_block`/`append_block`/`update_block`/`size`.
+pub trait RandomAccess: Send + Sync {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize>;
+    fn len(&self) -> Result<u64>;
+    fn truncate(&self, size: u64) -> Result<()>;
+}
+
+/// Blanket impl so any existing `Read + Write + Seek` backend can be boxed up
+/// as a `RandomAccess` without writing a dedicated wrapper type. The interior
+/// `Mutex` supplies the `&self` access that `RandomAccess` requires, exactly as
+/// `IoWrapper` already does for the non-boxed path.
+impl<T: Read + Write + Seek + Send> RandomAccess for Mutex<T> {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut inner = self
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Lock Error"))?;
+        inner.seek(SeekFrom::Start(offset))?;
+        let mut ret = 0;
+        loop {
+            let bytes_read = inner.read(&mut buf[ret..])?;
+            if bytes_read == 0 {
+                break;
+            }
+            ret += bytes_read;
+        }
+        Ok(ret)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize> {
+        let mut inner = self
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Lock Error"))?;
+        inner.seek(SeekFrom::Start(offset))?;
+        inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn len(&self) -> Result<u64> {
+        let mut inner = self
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Lock Error"))?;
+        inner.seek(SeekFrom::End(0))
+    }
+
+    fn truncate(&self, _size: u64) -> Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "truncate is not supported for this RandomAccess backend",
+        ))
+    }
+}
+
+impl RandFile<'_, ReadOnly, Box<dyn RandomAccess>> {
+    /// Create a read-only random file over a boxed, dynamically-dispatched
+    /// backend (see [`RandomAccess`]).
+    ///
+    /// Named distinctly from [`for_read_only`](RandFile::for_read_only)
+    /// rather than overloading it: both are inherent methods on `RandFile`,
+    /// and an un-turbofished call site like `RandFile::for_read_only(x)`
+    /// can't be disambiguated between a generic impl and a concrete one by
+    /// argument type alone, so overloading the name would break every
+    /// existing caller of the generic constructor with an ambiguity error.
+    pub fn for_read_only_boxed(inner: Box<dyn RandomAccess>) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl RandFile<'_, ReadWrite, Box<dyn RandomAccess>> {
+    /// Create a read-write random file over a boxed, dynamically-dispatched
+    /// backend (see [`RandomAccess`]).
+    ///
+    /// Named distinctly from [`for_read_write`](RandFile::for_read_write)
+    /// for the same reason as
+    /// [`for_read_only_boxed`](RandFile::for_read_only_boxed).
+    pub fn for_read_write_boxed(inner: Box<dyn RandomAccess>) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl<Mode: CanRead<Box<dyn RandomAccess>>> RandFile<'_, Mode, Box<dyn RandomAccess>> {
+    pub fn size(&mut self) -> Result<u64> {
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        inner.try_borrow(self.token)?.len()
+    }
+
+    /// Read a block from the boxed backend. See [`RandFile::read_block`] for
+    /// the semantics of the return value.
+    pub fn read_block(&mut self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        inner.try_borrow(self.token)?.read_at(addr, buf)
+    }
+}
+
+impl<Mode: CanWrite<Box<dyn RandomAccess>>> RandFile<'_, Mode, Box<dyn RandomAccess>> {
+    /// Append a block to the boxed backend. See [`RandFile::append_block`].
+    pub fn append_block(&mut self, buf: &[u8]) -> Result<u64> {
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        let backend = inner.try_borrow(self.token)?;
+        let offset = backend.len()?;
+        backend.write_at(offset, buf)?;
+        Ok(offset)
+    }
+
+    /// Update a data block in the boxed backend. See [`RandFile::update_block`].
+    pub fn update_block(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "LockError"))?;
+        inner.try_borrow(self.token)?.write_at(offset, buf)?;
+        Ok(())
+    }
+}
+
 pub mod mapping {
     use super::*;
 
@@ -263,9 +625,52 @@ pub mod mapping {
             drop(inner);
             Ok(MappingHandle(Arc::new(mapped)))
         }
+
+        /// Narrow this mapping to a typed, zero-copy view, e.g. a `&[i32]` of
+        /// coverage counts or a cast to a `repr(C)` header struct, instead of
+        /// re-parsing the raw bytes at every access. This is the
+        /// guard-transform pattern: `f` borrows from the mapped bytes and the
+        /// returned [`MappedView`] keeps the underlying `Arc<Mmap>` alive for
+        /// as long as that borrow is held.
+        pub fn map<U: ?Sized, F>(self, f: F) -> MappedView<U>
+        where
+            F: FnOnce(&[u8]) -> &U,
+        {
+            let view: *const U = f(self.0.as_ref());
+            MappedView {
+                _mmap: self.0,
+                view,
+            }
+        }
     }
 
-    #[derive(Clone)]
+    /// A typed, zero-copy view produced by [`MappingHandle::map`]. Derefs to
+    /// `U`; the mapping it was carved out of stays alive for as long as this
+    /// view does.
+    pub struct MappedView<U: ?Sized> {
+        _mmap: Arc<Mmap>,
+        view: *const U,
+    }
+
+    impl<U: ?Sized> std::ops::Deref for MappedView<U> {
+        type Target = U;
+        fn deref(&self) -> &U {
+            // Safe because `view` borrows from `_mmap`, which this struct keeps
+            // alive for its whole lifetime.
+            unsafe { &*self.view }
+        }
+    }
+
+    unsafe impl<U: ?Sized + Sync> Send for MappedView<U> {}
+    unsafe impl<U: ?Sized + Sync> Sync for MappedView<U> {}
+
+    /// Deliberately not `Clone` (unlike the read-only [`MappingHandle`]):
+    /// [`map_mut`](MappingHandleMut::map_mut) hands out a [`MappedViewMut`]
+    /// that derefs as an exclusive `&mut U` over the mapped bytes. If this
+    /// handle could be cloned, two clones could each call `map_mut` and
+    /// produce two live `&mut U` aliases over the identical memory, which is
+    /// undefined behavior, not just a logic bug -- so ownership of a given
+    /// mapped window has to stay with exactly one `MappingHandleMut`.
     pub struct MappingHandleMut(Arc<SyncGuard>, usize, usize);
 
     impl AsRef<[u8]> for MappingHandleMut {
@@ -304,6 +709,763 @@ pub mod mapping {
                 size,
             ))
         }
+
+        /// The mutable counterpart of [`MappingHandle::map`]: narrow this
+        /// mapping to a typed, zero-copy view that can also be written
+        /// through, keeping the mapping (and its flush-on-drop `SyncGuard`)
+        /// alive for as long as the returned [`MappedViewMut`] is.
+        pub fn map_mut<U: ?Sized, F>(mut self, f: F) -> MappedViewMut<U>
+        where
+            F: FnOnce(&mut [u8]) -> &mut U,
+        {
+            let view: *mut U = f(self.as_mut());
+            MappedViewMut {
+                _mmap: self.0,
+                view,
+            }
+        }
+    }
+
+    /// A typed, zero-copy mutable view produced by
+    /// [`MappingHandleMut::map_mut`]. Derefs (mutably) to `U`; the mapping it
+    /// was carved out of, and its flush-on-drop behavior, stay alive for as
+    /// long as this view does.
+    pub struct MappedViewMut<U: ?Sized> {
+        _mmap: Arc<SyncGuard>,
+        view: *mut U,
+    }
+
+    impl<U: ?Sized> std::ops::Deref for MappedViewMut<U> {
+        type Target = U;
+        fn deref(&self) -> &U {
+            unsafe { &*self.view }
+        }
+    }
+
+    impl<U: ?Sized> std::ops::DerefMut for MappedViewMut<U> {
+        fn deref_mut(&mut self) -> &mut U {
+            unsafe { &mut *self.view }
+        }
+    }
+
+    // `MappedViewMut` behaves like an owned `&mut U`: `&mut U: Send` iff
+    // `U: Send` (not `U: Sync` — that's the rule for `&mut U: Sync`, below).
+    // Bounding `Send` on `Sync` instead would let a `Sync`-but-not-`Send`
+    // type (e.g. something `MutexGuard`-shaped, which must be dropped on the
+    // thread that acquired it) cross threads through this wrapper.
+    unsafe impl<U: ?Sized + Send> Send for MappedViewMut<U> {}
+    unsafe impl<U: ?Sized + Sync> Sync for MappedViewMut<U> {}
+}
+
+pub mod file_lock {
+    //! Advisory, cross-process byte-range locking for [`File`](super::File)
+    //! backends, built on `fcntl(F_SETLK`/`F_SETLKW)`. Locks are scoped to
+    //! the byte range a writer is about to touch instead of the whole file.
+
+    use super::Duration;
+    use std::fs::File;
+    use std::io::{Error, ErrorKind, Result};
+    use std::time::Instant;
+
+    #[cfg(unix)]
+    pub(super) fn raw_fd_of(file: &File) -> std::os::unix::io::RawFd {
+        std::os::unix::io::AsRawFd::as_raw_fd(file)
+    }
+
+    #[cfg(unix)]
+    pub type RawHandle = std::os::unix::io::RawFd;
+    #[cfg(not(unix))]
+    pub type RawHandle = ();
+
+    #[cfg(not(unix))]
+    pub(super) fn raw_fd_of(_file: &File) -> RawHandle {}
+
+    /// A held advisory lock on a byte range of a file. The lock is released
+    /// when this guard is dropped.
+    pub struct FileLockGuard {
+        handle: RawHandle,
+        offset: u64,
+        len: u64,
+    }
+
+    #[cfg(unix)]
+    impl Drop for FileLockGuard {
+        fn drop(&mut self) {
+            let lock = make_flock(libc::F_UNLCK as libc::c_short, self.offset, self.len);
+            unsafe {
+                libc::fcntl(self.handle, libc::F_SETLK, &lock);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    impl Drop for FileLockGuard {
+        fn drop(&mut self) {}
+    }
+
+    #[cfg(unix)]
+    fn make_flock(kind: libc::c_short, offset: u64, len: u64) -> libc::flock {
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = kind;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        lock.l_start = offset as libc::off_t;
+        lock.l_len = len as libc::off_t;
+        lock
+    }
+
+    #[cfg(unix)]
+    fn try_lock_once(handle: RawHandle, offset: u64, len: u64) -> Result<bool> {
+        let lock = make_flock(libc::F_WRLCK as libc::c_short, offset, len);
+        let rc = unsafe { libc::fcntl(handle, libc::F_SETLK, &lock) };
+        if rc == 0 {
+            Ok(true)
+        } else {
+            match Error::last_os_error().raw_os_error() {
+                Some(libc::EACCES) | Some(libc::EAGAIN) => Ok(false),
+                _ => Err(Error::last_os_error()),
+            }
+        }
+    }
+
+    /// Block (spinning on `F_SETLK`, sleeping between attempts) until the
+    /// `[offset, offset + len)` range can be exclusively locked, or until
+    /// `timeout` elapses, in which case `Err` with `ErrorKind::WouldBlock` is
+    /// returned. `timeout = None` waits indefinitely via `F_SETLKW`.
+    #[cfg(unix)]
+    pub(super) fn lock_range(
+        handle: RawHandle,
+        offset: u64,
+        len: u64,
+        timeout: Option<Duration>,
+    ) -> Result<FileLockGuard> {
+        match timeout {
+            None => {
+                let lock = make_flock(libc::F_WRLCK as libc::c_short, offset, len);
+                let rc = unsafe { libc::fcntl(handle, libc::F_SETLKW, &lock) };
+                if rc != 0 {
+                    return Err(Error::last_os_error());
+                }
+            }
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                while !try_lock_once(handle, offset, len)? {
+                    if Instant::now() >= deadline {
+                        return Err(Error::new(
+                            ErrorKind::WouldBlock,
+                            "timed out waiting for file lock",
+                        ));
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+        Ok(FileLockGuard {
+            handle,
+            offset,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(super) fn lock_range(
+        _handle: RawHandle,
+        offset: u64,
+        len: u64,
+        _timeout: Option<Duration>,
+    ) -> Result<FileLockGuard> {
+        Err(Error::new(
+            ErrorKind::Other,
+            "advisory file locking is only implemented on unix",
+        ))
+    }
+}
+
+/// An async counterpart of [`RandFile`] for tokio-based pipelines.
+///
+/// `RandFile` blocks the calling thread for the duration of every read/write,
+/// which is fine for a CLI but starves a tokio executor when a server wants
+/// to stream many D4 tracks concurrently. `AsyncRandFile` backs onto
+/// `tokio::fs::File` and an async mutex instead, so `read_block`/
+/// `append_block`/`update_block`/`size` yield the executor thread while
+/// waiting on I/O rather than blocking it outright.
+pub mod asynchronous {
+    use std::io::{Result, SeekFrom};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::fs::File;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+    use tokio::sync::Mutex;
+
+    /// The async analogue of [`RandFile`](super::RandFile). It preserves the
+    /// same token-based exclusivity semantics that [`RandFile::lock`]
+    /// provides: cloning an `AsyncRandFile` shares the same underlying file
+    /// and token, while [`lock`](AsyncRandFile::lock) mints a new, higher
+    /// token that temporarily shuts out all other handles until it (and every
+    /// clone of it) is dropped. Unlike `RandFile`, the per-token refcount is
+    /// an `AtomicU32` rather than a field behind the state mutex, so `Clone`
+    /// and `Drop` never need to await (or block on) the async lock.
+    pub struct AsyncRandFile {
+        inner: Arc<Mutex<AsyncIoState>>,
+        token: u32,
+        refcount: Arc<AtomicU32>,
+    }
+
+    impl Clone for AsyncRandFile {
+        fn clone(&self) -> Self {
+            self.refcount.fetch_add(1, Ordering::SeqCst);
+            AsyncRandFile {
+                inner: self.inner.clone(),
+                token: self.token,
+                refcount: self.refcount.clone(),
+            }
+        }
+    }
+
+    struct AsyncIoState {
+        inner: File,
+        current_token: u32,
+        token_stack: Vec<(Arc<AtomicU32>, Box<dyn FnOnce() + Send>)>,
+    }
+
+    impl AsyncIoState {
+        fn try_borrow_mut(&mut self, token: u32) -> Result<&mut File> {
+            if token == self.current_token {
+                Ok(&mut self.inner)
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Rand file locked",
+                ))
+            }
+        }
+    }
+
+    impl AsyncRandFile {
+        /// Wrap an already-open `tokio::fs::File`.
+        pub fn new(inner: File) -> Self {
+            let refcount = Arc::new(AtomicU32::new(1));
+            AsyncRandFile {
+                inner: Arc::new(Mutex::new(AsyncIoState {
+                    inner,
+                    current_token: 0,
+                    token_stack: vec![(refcount.clone(), Box::new(|| ()))],
+                })),
+                token: 0,
+                refcount,
+            }
+        }
+
+        /// Open `path` for reading and writing asynchronously, as with
+        /// [`RandFile::for_read_write`](super::RandFile::for_read_write).
+        pub async fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+            let file = tokio::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(path)
+                .await?;
+            Ok(Self::new(file))
+        }
+
+        /// Acquire exclusive access, running `update_fn` once every handle
+        /// derived from the returned lock has been dropped again. Mirrors
+        /// [`RandFile::lock`](super::RandFile::lock).
+        pub async fn lock(&self, update_fn: Box<dyn FnOnce() + Send>) -> Self {
+            let refcount = Arc::new(AtomicU32::new(1));
+            let mut inner = self.inner.lock().await;
+            inner.current_token += 1;
+            inner.token_stack.push((refcount.clone(), update_fn));
+            let token = inner.current_token;
+            drop(inner);
+            AsyncRandFile {
+                inner: self.inner.clone(),
+                token,
+                refcount,
+            }
+        }
+
+        pub async fn size(&self) -> Result<u64> {
+            let mut inner = self.inner.lock().await;
+            inner.try_borrow_mut(self.token)?.seek(SeekFrom::End(0)).await
+        }
+
+        /// Read a block, async counterpart of
+        /// [`RandFile::read_block`](super::RandFile::read_block).
+        pub async fn read_block(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+            let mut inner = self.inner.lock().await;
+            let file = inner.try_borrow_mut(self.token)?;
+            file.seek(SeekFrom::Start(addr)).await?;
+            let mut ret = 0;
+            loop {
+                let bytes_read = file.read(&mut buf[ret..]).await?;
+                if bytes_read == 0 {
+                    break Ok(ret);
+                }
+                ret += bytes_read;
+            }
+        }
+
+        /// Append a block, async counterpart of
+        /// [`RandFile::append_block`](super::RandFile::append_block).
+        pub async fn append_block(&self, buf: &[u8]) -> Result<u64> {
+            let mut inner = self.inner.lock().await;
+            let file = inner.try_borrow_mut(self.token)?;
+            let ret = file.seek(SeekFrom::End(0)).await?;
+            file.write_all(buf).await?;
+            Ok(ret)
+        }
+
+        /// Update a block, async counterpart of
+        /// [`RandFile::update_block`](super::RandFile::update_block).
+        pub async fn update_block(&self, offset: u64, buf: &[u8]) -> Result<()> {
+            let mut inner = self.inner.lock().await;
+            let file = inner.try_borrow_mut(self.token)?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.write_all(buf).await?;
+            Ok(())
+        }
+    }
+
+    /// Pop every token off the top of the stack whose refcount has reached
+    /// zero, returning their update callbacks for the caller to run once the
+    /// state lock is released. Shared between the `Drop` impl's
+    /// spawned-task and synchronous-fallback paths below.
+    fn pop_released_tokens(inner: &mut AsyncIoState) -> Vec<Box<dyn FnOnce() + Send>> {
+        let mut update_callbacks = vec![];
+        while inner.current_token > 0
+            && inner.token_stack[inner.current_token as usize]
+                .0
+                .load(Ordering::SeqCst)
+                == 0
+        {
+            inner.current_token -= 1;
+            if let Some((_, update)) = inner.token_stack.pop() {
+                update_callbacks.push(update);
+            }
+        }
+        update_callbacks
+    }
+
+    impl Drop for AsyncRandFile {
+        fn drop(&mut self) {
+            if self.refcount.fetch_sub(1, Ordering::SeqCst) != 1 {
+                // Another clone of this handle is still alive.
+                return;
+            }
+            // This was the last handle for `self.token`. Popping the stack
+            // and running the update callback needs the async state mutex,
+            // which `Drop` can't await, so hand it to a detached task --
+            // but only if there's a runtime to spawn it on. Dropping the
+            // very last `AsyncRandFile` (e.g. during process or plain
+            // synchronous-test teardown, outside any
+            // `#[tokio::main]`/`#[tokio::test]` context) has no current
+            // runtime, and `tokio::spawn` panics in that case. Fall back to
+            // an uncontended `try_lock`, which needs no runtime at all; if
+            // even that doesn't succeed, skip the cleanup rather than
+            // panic in `Drop` -- the state mutex being held elsewhere at
+            // this exact moment with no runtime around to unblock it is
+            // degenerate enough that "the update callback runs late or not
+            // at all" is the least bad outcome.
+            let state = self.inner.clone();
+            if tokio::runtime::Handle::try_current().is_ok() {
+                tokio::spawn(async move {
+                    let mut inner = state.lock().await;
+                    let update_callbacks = pop_released_tokens(&mut inner);
+                    drop(inner);
+                    update_callbacks.into_iter().for_each(|f| f());
+                });
+            } else if let Ok(mut inner) = state.try_lock() {
+                let update_callbacks = pop_released_tokens(&mut inner);
+                drop(inner);
+                update_callbacks.into_iter().for_each(|f| f());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_read_write_blocks() {
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_async_randfile_read_write_test.bin");
+            let file = AsyncRandFile::open(&path).await.unwrap();
+
+            assert_eq!(0, file.append_block(b"hello world").await.unwrap());
+            assert_eq!(11, file.append_block(b"!!!").await.unwrap());
+
+            let mut buf = [0u8; 11];
+            assert_eq!(11, file.read_block(0, &mut buf).await.unwrap());
+            assert_eq!(b"hello world", &buf);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_lock_excludes_other_handles() {
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_async_randfile_lock_test.bin");
+            let file = AsyncRandFile::open(&path).await.unwrap();
+            file.append_block(b"before lock").await.unwrap();
+
+            let unlocked = Arc::new(AtomicU32::new(0));
+            let locked = {
+                let unlocked = unlocked.clone();
+                file.lock(Box::new(move || {
+                    unlocked.store(1, Ordering::SeqCst);
+                }))
+                .await
+            };
+
+            // The original handle is shut out while `locked` (and its
+            // clones) are alive.
+            assert!(file.append_block(b"denied").await.is_err());
+
+            drop(locked);
+            // Dropping the last handle for a token spawns a task (we're in
+            // a `#[tokio::test]` runtime here) to pop it back off; give it
+            // a chance to run.
+            tokio::task::yield_now().await;
+            assert_eq!(1, unlocked.load(Ordering::SeqCst));
+
+            file.append_block(b" after lock").await.unwrap();
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_drop_outside_runtime_does_not_panic() {
+            // Regression test: `tokio::spawn` panics with no current
+            // runtime, which is exactly the situation dropping the last
+            // `AsyncRandFile` handle in a plain (non-`#[tokio::test]`)
+            // test like this one creates.
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_async_randfile_drop_test.bin");
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let file = runtime.block_on(AsyncRandFile::open(&path)).unwrap();
+            drop(runtime);
+
+            drop(file);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
+
+pub mod ring_buffer {
+    //! A paged, memory-mapped writer for streaming appends.
+    //!
+    //! [`RandFile::append_block`] does a `seek(End)` + `write_all` under the
+    //! global lock on every call, which is wasteful when emitting millions of
+    //! tiny coverage deltas. [`RingBufferWriter`] instead keeps a window of
+    //! mapped pages ahead of the append cursor; writes copy straight into the
+    //! mapping with no syscall per block, and the window is flushed and
+    //! remapped further into the file only once it fills up.
+
+    use super::mapping::MappingHandleMut;
+    use super::{File, RandFile, ReadWrite};
+    use std::io::Result;
+    use std::mem::ManuallyDrop;
+
+    const DEFAULT_PAGE_SIZE: usize = 4096;
+
+    /// Join a background window-flush thread, turning a panic into an
+    /// `Err` instead of silently swallowing it. The flushed window's
+    /// `SyncGuard::drop` panics if `msync` fails, so a discarded `join()`
+    /// result here would hide a real flush failure from every caller.
+    fn join_flush(handle: std::thread::JoinHandle<()>) -> Result<()> {
+        handle.join().map_err(|panic| {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "background window flush panicked".to_string());
+            std::io::Error::new(std::io::ErrorKind::Other, msg)
+        })
+    }
+
+    /// A [`RandFile`] writer that batches appends into a memory-mapped
+    /// window, trading per-block syscalls for periodic, page-granularity
+    /// remapping.
+    pub struct RingBufferWriter {
+        file: ManuallyDrop<RandFile<'static, ReadWrite, File>>,
+        page_size: usize,
+        window_pages: usize,
+        /// Absolute file offset the current window's mapping begins at.
+        window_start: u64,
+        /// Write position within the current window, in bytes.
+        cursor: usize,
+        /// `None` only for the brief moment `into_inner`/`Drop` are tearing
+        /// the writer down.
+        window: Option<MappingHandleMut>,
+        /// The background flush of the previously-completed window, if one
+        /// is still in flight. Joined before starting another, so at most
+        /// one flush thread is ever outstanding.
+        pending_flush: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl RingBufferWriter {
+        /// Wrap `file` in a ring-buffer writer backed by `window_pages` pages
+        /// of `DEFAULT_PAGE_SIZE` (4 KiB), mapped ahead of the current end-of-file.
+        pub fn new(file: RandFile<'static, ReadWrite, File>, window_pages: usize) -> Result<Self> {
+            Self::with_page_size(file, window_pages, DEFAULT_PAGE_SIZE)
+        }
+
+        /// Like [`new`](RingBufferWriter::new), but with an explicit page
+        /// size instead of the default 4 KiB.
+        pub fn with_page_size(
+            mut file: RandFile<'static, ReadWrite, File>,
+            window_pages: usize,
+            page_size: usize,
+        ) -> Result<Self> {
+            let window_start = file.size()?;
+            file.set_len(window_start + (window_pages * page_size) as u64)?;
+            let window = file.mmap_mut(window_start, window_pages * page_size)?;
+            Ok(RingBufferWriter {
+                file: ManuallyDrop::new(file),
+                page_size,
+                window_pages,
+                window_start,
+                cursor: 0,
+                window: Some(window),
+                pending_flush: None,
+            })
+        }
+
+        fn window_len(&self) -> usize {
+            self.window_pages * self.page_size
+        }
+
+        /// Make sure the file is exactly `start + window_len()` bytes long,
+        /// i.e. just enough to map the next window.
+        ///
+        /// This sets the length outright instead of calling
+        /// [`RandFile::reserve_block`], which extends relative to whatever
+        /// the file's *actual* current length happens to be. Since a rolled
+        /// window is usually only partially filled, its unused tail is real
+        /// slack the file was over-reserved by; reserving the next window
+        /// relative to that (rather than to `start`) would carry that slack
+        /// forward and let it compound with every rollover. Setting the
+        /// length to `start + window_len()` directly collapses any such
+        /// slack back out at every rollover, so the file is never more than
+        /// one window ahead of the data actually written to it.
+        fn reserve_window(&mut self, start: u64) -> Result<()> {
+            let window_len = self.window_len() as u64;
+            self.file.set_len(start + window_len)
+        }
+
+        /// Append a block the same way [`RandFile::append_block`] would,
+        /// returning its absolute file offset, but writing into the mapped
+        /// window instead of issuing a `seek` + `write` syscall pair.
+        pub fn append_block(&mut self, buf: &[u8]) -> Result<u64> {
+            if buf.len() > self.window_len() {
+                // Too big to ever fit in one window; fall back to a regular
+                // append rather than trying to special-case spanning writes.
+                return self.append_oversized(buf);
+            }
+            if self.cursor + buf.len() > self.window_len() {
+                self.advance_window()?;
+            }
+            let offset = self.window_start + self.cursor as u64;
+            let window = self.window.as_mut().expect("window taken");
+            window.as_mut()[self.cursor..self.cursor + buf.len()].copy_from_slice(buf);
+            self.cursor += buf.len();
+            Ok(offset)
+        }
+
+        /// Handle a block too large for the window. The window sits inside a
+        /// file that's over-reserved ahead of the real, logical end of data
+        /// (`window_start + cursor`), so a plain `self.file.append_block`
+        /// here would land past that and leave a gap; trim the
+        /// over-reservation back to the logical end first, then append for
+        /// real, then open a fresh window beyond it.
+        fn append_oversized(&mut self, buf: &[u8]) -> Result<u64> {
+            self.flush_tail()?;
+            let offset = self.file.append_block(buf)?;
+            let next_start = offset + buf.len() as u64;
+            self.reserve_window(next_start)?;
+            let window_len = self.window_len();
+            self.window = Some(self.file.mmap_mut(next_start, window_len)?);
+            self.window_start = next_start;
+            self.cursor = 0;
+            Ok(offset)
+        }
+
+        /// Flush the current window and map the next one in, further into
+        /// the file.
+        fn advance_window(&mut self) -> Result<()> {
+            let next_start = self.window_start + self.cursor as u64;
+            if let Some(old_window) = self.window.take() {
+                // The completed window's `Drop` (via its `SyncGuard`) does a
+                // blocking `msync`; run it off the append path so the next
+                // write isn't stalled behind it. Joining any previous flush
+                // first caps the number of these in flight at once, rather
+                // than letting a fast rollover rate spawn one per page
+                // forever.
+                if let Some(handle) = self.pending_flush.take() {
+                    join_flush(handle)?;
+                }
+                self.pending_flush = Some(std::thread::spawn(move || drop(old_window)));
+            }
+            self.reserve_window(next_start)?;
+            let window_len = self.window_len();
+            self.window = Some(self.file.mmap_mut(next_start, window_len)?);
+            self.window_start = next_start;
+            self.cursor = 0;
+            Ok(())
+        }
+
+        /// Flush the tail (partially-filled) window and trim the file back
+        /// to the real amount of data written, undoing the over-reservation
+        /// `advance_window`/`new` did to get a mappable window.
+        fn flush_tail(&mut self) -> Result<()> {
+            if let Some(handle) = self.pending_flush.take() {
+                join_flush(handle)?;
+            }
+            if let Some(window) = self.window.take() {
+                drop(window);
+            }
+            let real_len = self.window_start + self.cursor as u64;
+            self.file.set_len(real_len)
+        }
+
+        /// Flush the tail window, trim the file, and hand back the
+        /// underlying [`RandFile`]. The resulting file is byte-for-byte
+        /// identical to one produced by the equivalent sequence of
+        /// `append_block` calls.
+        pub fn into_inner(mut self) -> Result<RandFile<'static, ReadWrite, File>> {
+            self.flush_tail()?;
+            // Safety: `file` is not touched again, and `self` is forgotten
+            // immediately below, so `Drop` won't also try to tear it down.
+            let file = unsafe { ManuallyDrop::take(&mut self.file) };
+            std::mem::forget(self);
+            Ok(file)
+        }
+    }
+
+    impl Drop for RingBufferWriter {
+        fn drop(&mut self) {
+            // Matches `SyncGuard::drop` above: a failed tail flush means the
+            // last window's data may not have made it to disk, which isn't
+            // safe to paper over by dropping it silently.
+            self.flush_tail().expect("RingBufferWriter flush on drop");
+            // Safety: nothing borrows from `self.file` after this point.
+            unsafe { ManuallyDrop::drop(&mut self.file) };
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::randfile::RandFile;
+
+        #[test]
+        fn test_append_and_rollover() {
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_ring_buffer_rollover_test.bin");
+            // Mapping the file read-write needs a descriptor opened for
+            // reading as well, which `File::create` alone does not give.
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            let rand_file = RandFile::for_read_write(file);
+            // A single 16-byte page, so the third append has to roll the
+            // window over.
+            let mut writer = RingBufferWriter::with_page_size(rand_file, 1, 16).unwrap();
+
+            assert_eq!(0, writer.append_block(b"abcdefgh").unwrap());
+            assert_eq!(8, writer.append_block(b"ijklmnop").unwrap());
+            assert_eq!(16, writer.append_block(b"q").unwrap());
+
+            let mut rand_file = writer.into_inner().unwrap();
+            assert_eq!(17, rand_file.size().unwrap());
+            let mut buf = [0u8; 17];
+            assert_eq!(17, rand_file.read_block(0, &mut buf).unwrap());
+            assert_eq!(b"abcdefghijklmnopq", &buf);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_oversized_block() {
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_ring_buffer_oversized_test.bin");
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            let rand_file = RandFile::for_read_write(file);
+            // An 8-byte window, so the middle block can't possibly fit and
+            // must go through the oversized fallback.
+            let mut writer = RingBufferWriter::with_page_size(rand_file, 1, 8).unwrap();
+
+            let big = vec![b'x'; 32];
+            assert_eq!(0, writer.append_block(b"ab").unwrap());
+            assert_eq!(2, writer.append_block(&big).unwrap());
+            assert_eq!(34, writer.append_block(b"cd").unwrap());
+
+            let mut rand_file = writer.into_inner().unwrap();
+            assert_eq!(36, rand_file.size().unwrap());
+            let mut buf = vec![0u8; 36];
+            assert_eq!(36, rand_file.read_block(0, &mut buf).unwrap());
+            assert_eq!(b"ab", &buf[0..2]);
+            assert_eq!(&big[..], &buf[2..34]);
+            assert_eq!(b"cd", &buf[34..36]);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_window_reservation_does_not_compound() {
+            // Regression test for `reserve_window`: before the fix, each
+            // rollover extended the file relative to its *actual* current
+            // length rather than the new window's logical end, so unused
+            // tail slack from one rollover carried into the next and grew
+            // without bound. Repeatedly writing two blocks that together
+            // overflow the window, but each leave roughly half the window
+            // unused at the point of rollover, maximizes that per-rollover
+            // slack; the file should still never be more than one window
+            // ahead of the data actually written, no matter how many
+            // rollovers have happened.
+            let dir = std::env::temp_dir();
+            let path = dir.join("d4_ring_buffer_slack_test.bin");
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            let rand_file = RandFile::for_read_write(file);
+            let window_len = 64usize;
+            let mut writer = RingBufferWriter::with_page_size(rand_file, 1, window_len).unwrap();
+
+            let block = vec![b'x'; window_len / 2 + 1];
+            let mut written = 0u64;
+            for _ in 0..50 {
+                writer.append_block(&block).unwrap();
+                written += block.len() as u64;
+
+                let file_len = writer.file.size().unwrap();
+                assert!(
+                    file_len - written <= window_len as u64,
+                    "slack grew unbounded: file_len={file_len}, written={written}"
+                );
+            }
+
+            let mut rand_file = writer.into_inner().unwrap();
+            assert_eq!(written, rand_file.size().unwrap());
+
+            std::fs::remove_file(&path).unwrap();
+        }
     }
 }
 
@@ -332,6 +1494,125 @@ mod test {
         assert_eq!(b"This is a test block", &buf);
     }
 
+    #[test]
+    fn test_read_block_at_positional() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("d4_randfile_positional_test.bin");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"This is a test block").unwrap();
+        }
+        let file = File::open(&path).unwrap();
+        let rand_file = RandFile::for_read_only_positional(file).unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            4,
+            rand_file.read_block_at(5, &mut buf).unwrap().unwrap()
+        );
+        assert_eq!(b"is a", &buf);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_block_prefers_positional_backend() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("d4_randfile_read_block_positional_test.bin");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"This is a test block").unwrap();
+        }
+        let file = File::open(&path).unwrap();
+        let mut rand_file = RandFile::for_read_only_positional(file).unwrap();
+
+        // `read_block` takes `&mut self`, but with a positional backend
+        // configured it should service this through the positional read
+        // rather than the seek-then-read path.
+        let mut buf = [0u8; 4];
+        assert_eq!(4, rand_file.read_block(5, &mut buf).unwrap());
+        assert_eq!(b"is a", &buf);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_positional_read_honors_lock() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("d4_randfile_positional_lock_test.bin");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"This is a test block").unwrap();
+        }
+        let file = File::open(&path).unwrap();
+        let mut rand_file = RandFile::for_read_only_positional(file).unwrap();
+
+        let mut buf = [0u8; 4];
+        let _locked = rand_file.lock(Box::new(|| ())).unwrap();
+
+        // A handle predating an active `lock()` must be shut out of the
+        // positional fast path exactly like every other operation here,
+        // not silently read straight through it.
+        assert!(rand_file.read_block(0, &mut buf).is_err());
+        assert!(rand_file.read_block_at(0, &mut buf).unwrap().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_block_at_without_positional_backend() {
+        let backend = Cursor::new(vec![0; 0]);
+        let rand_file = RandFile::for_read_write(backend);
+        let mut buf = [0u8; 4];
+        assert!(rand_file.read_block_at(0, &mut buf).is_none());
+    }
+
+    #[test]
+    fn test_boxed_random_access_backend() {
+        let backend: Box<dyn RandomAccess> = Box::new(Mutex::new(Cursor::new(vec![0; 0])));
+        let mut rand_file = RandFile::for_read_write_boxed(backend);
+        assert_eq!(0, rand_file.append_block(b"This is a test block").unwrap());
+        assert_eq!(20, rand_file.append_block(b"This is a test block").unwrap());
+
+        let mut buf = [0u8; 20];
+        assert_eq!(20, rand_file.read_block(0, &mut buf).unwrap());
+        assert_eq!(b"This is a test block", &buf);
+    }
+
+    #[test]
+    fn test_append_block_locked() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("d4_randfile_append_locked_test.bin");
+        // Opened read-write: the test reads the appended block back.
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let mut rand_file = RandFile::for_read_write(file);
+
+        assert_eq!(
+            0,
+            rand_file
+                .append_block_locked(b"This is a test block", Some(Duration::from_secs(1)))
+                .unwrap()
+        );
+        assert_eq!(
+            20,
+            rand_file
+                .append_block_locked(b"This is a test block", Some(Duration::from_secs(1)))
+                .unwrap()
+        );
+
+        let mut buf = [0u8; 20];
+        assert_eq!(20, rand_file.read_block(0, &mut buf).unwrap());
+        assert_eq!(b"This is a test block", &buf);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_lock() {
         let backend = Cursor::new(vec![0; 0]);